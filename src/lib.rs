@@ -111,10 +111,13 @@
 #[macro_use] extern crate slog;
 extern crate rocket;
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use slog::Logger;
 use rocket::{Data, Request, Response, Rocket, State};
+use rocket::http::Status;
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::request;
 
@@ -122,14 +125,170 @@ use rocket::request;
 #[derive(Debug, Clone)]
 pub struct SyncLogger(Arc<Logger>);
 
+/// Per-request state stashed in Rocket's request-local cache by `on_request`,
+/// and read back by both `SyncLogger::from_request` and `on_response`.
+struct RequestLog {
+    logger: Arc<Logger>,
+    start: Instant,
+}
+
+/// The slog::Level used for each class of lifecycle event the fairing emits.
+///
+/// Defaults to `Info` everywhere, matching the fairing's previous behavior. The level of
+/// `response` logs is driven by the response status instead; see `SlogFairing::status_level`.
+#[derive(Debug, Clone, Copy)]
+pub struct LogLevels {
+    pub config: slog::Level,
+    pub routes: slog::Level,
+    pub launch: slog::Level,
+    pub request: slog::Level,
+}
+
+impl Default for LogLevels {
+    fn default() -> LogLevels {
+        LogLevels {
+            config: slog::Level::Info,
+            routes: slog::Level::Info,
+            launch: slog::Level::Info,
+            request: slog::Level::Info,
+        }
+    }
+}
+
+/// Default status -> slog::Level mapping: 2xx/3xx at info, 4xx at warn, 5xx at error.
+fn default_status_level(status: &Status) -> slog::Level {
+    match status.code {
+        500..=599 => slog::Level::Error,
+        400..=499 => slog::Level::Warning,
+        _ => slog::Level::Info,
+    }
+}
+
+/// A matcher used to decide whether a request/response pair should be skipped
+/// by the fairing's request/response logging.
+enum RequestFilter {
+    Path(String),
+    Prefix(String),
+    Predicate(Box<dyn Fn(&Request) -> bool + Send + Sync>),
+}
+
+/// Extra structured fields contributed by a `SlogFairing::request_context` closure.
+pub type ContextFields = Vec<(&'static str, String)>;
+
+/// Adapts `ContextFields` to slog's `KV` trait so it can be folded into a log record.
+struct RequestContextKV(ContextFields);
+
+impl slog::KV for RequestContextKV {
+    fn serialize(&self, _record: &slog::Record, serializer: &mut dyn slog::Serializer) -> slog::Result {
+        for (key, value) in &self.0 {
+            serializer.emit_str(key, value)?;
+        }
+        Ok(())
+    }
+}
+
 /// Fairing used to provide a rocket.rs application with a slog::Logger
-#[derive(Debug, Clone)]
-pub struct SlogFairing(SyncLogger);
+pub struct SlogFairing {
+    logger: SyncLogger,
+    next_request_id: AtomicU64,
+    filters: Vec<RequestFilter>,
+    levels: LogLevels,
+    status_level: Box<dyn Fn(&Status) -> slog::Level + Send + Sync>,
+    request_context: Option<Box<dyn Fn(&Request) -> ContextFields + Send + Sync>>,
+}
 
 impl SlogFairing {
     /// Create a new SlogFairing using the slog::Logger
     pub fn new(root_logger: Logger) -> SlogFairing {
-        SlogFairing(SyncLogger(Arc::new(root_logger)))
+        SlogFairing {
+            logger: SyncLogger(Arc::new(root_logger)),
+            next_request_id: AtomicU64::new(0),
+            filters: Vec::new(),
+            levels: LogLevels::default(),
+            status_level: Box::new(default_status_level),
+            request_context: None,
+        }
+    }
+
+    /// Contribute extra structured fields to every `request`/`response` log record, e.g. the
+    /// client IP or user agent pulled from the incoming request.
+    pub fn request_context<F>(mut self, request_context: F) -> SlogFairing
+    where
+        F: Fn(&Request) -> ContextFields + Send + Sync + 'static,
+    {
+        self.request_context = Some(Box::new(request_context));
+        self
+    }
+
+    /// Builds `base` augmented with the fields returned by the configured `request_context`
+    /// closure for `request`, if any.
+    fn contextualize(&self, base: Logger, request: &Request) -> Logger {
+        match self.request_context {
+            Some(ref request_context) => {
+                base.new(slog::OwnedKV(RequestContextKV(request_context(request))))
+            }
+            None => base,
+        }
+    }
+
+    /// Configure the slog::Level used for each class of lifecycle event.
+    pub fn levels(mut self, levels: LogLevels) -> SlogFairing {
+        self.levels = levels;
+        self
+    }
+
+    /// Override how a response's HTTP status is mapped to the slog::Level its `response`
+    /// log line is emitted at. Defaults to 2xx/3xx at info, 4xx at warn, 5xx at error.
+    pub fn status_level<F>(mut self, status_level: F) -> SlogFairing
+    where
+        F: Fn(&Status) -> slog::Level + Send + Sync + 'static,
+    {
+        self.status_level = Box::new(status_level);
+        self
+    }
+
+    /// Don't emit `request`/`response` log lines for requests whose path is exactly `path`.
+    pub fn ignore_path<S: Into<String>>(mut self, path: S) -> SlogFairing {
+        self.filters.push(RequestFilter::Path(path.into()));
+        self
+    }
+
+    /// Don't emit `request`/`response` log lines for requests whose path starts with `prefix`.
+    pub fn ignore_prefix<S: Into<String>>(mut self, prefix: S) -> SlogFairing {
+        self.filters.push(RequestFilter::Prefix(prefix.into()));
+        self
+    }
+
+    /// Don't emit `request`/`response` log lines for requests matching an arbitrary predicate.
+    pub fn filter<F>(mut self, predicate: F) -> SlogFairing
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push(RequestFilter::Predicate(Box::new(predicate)));
+        self
+    }
+
+    /// Whether `request` matches one of the configured filters and should be left out of the logs.
+    fn is_ignored(&self, request: &Request) -> bool {
+        let path = request.uri().path();
+        self.filters.iter().any(|filter| match *filter {
+            RequestFilter::Path(ref p) => p == path,
+            RequestFilter::Prefix(ref prefix) => path.starts_with(prefix.as_str()),
+            RequestFilter::Predicate(ref predicate) => predicate(request),
+        })
+    }
+}
+
+impl std::fmt::Debug for SlogFairing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SlogFairing")
+            .field("logger", &self.logger)
+            .field("next_request_id", &self.next_request_id.load(Ordering::Relaxed))
+            .field("filters", &self.filters.len())
+            .field("levels", &self.levels)
+            .field("status_level", &"<closure>")
+            .field("request_context", &self.request_context.is_some())
+            .finish()
     }
 }
 
@@ -151,8 +310,15 @@ impl<'a, 'r> request::FromRequest<'a, 'r> for SyncLogger {
     type Error = ();
 
     fn from_request(req: &'a request::Request<'r>) -> request::Outcome<SyncLogger, ()> {
-        let sync_logger = req.guard::<State<SyncLogger>>()?;
-        rocket::Outcome::Success(sync_logger.clone())
+        // `on_request` always populates this before any handler runs; the fallback
+        // only matters if someone calls this guard outside of a fairing-managed request.
+        let request_log = req.local_cache(|| RequestLog {
+            logger: req.guard::<State<SyncLogger>>()
+                .map(|logger| logger.0.clone())
+                .unwrap_or_else(|_| Arc::new(Logger::root(slog::Discard, o!()))),
+            start: Instant::now(),
+        });
+        rocket::Outcome::Success(SyncLogger(request_log.logger.clone()))
     }
 }
 
@@ -167,35 +333,36 @@ impl Fairing for SlogFairing {
     fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
         {
             let config = rocket.config();
-            slog_info!(&self.0, "config"; "key" => "environment", "value" => ?config.environment);
-            slog_info!(&self.0, "config"; "key" => "address", "value" => %config.address);
-            slog_info!(&self.0, "config"; "key" => "port", "value" => %config.port);
-            slog_info!(&self.0, "config"; "key" => "workers", "value" => %config.workers);
-            slog_info!(&self.0, "config"; "key" => "log_level", "value" => ?config.log_level);
+            let lvl = self.levels.config;
+            slog_log!(&self.logger, lvl, "", "config"; "key" => "environment", "value" => ?config.environment);
+            slog_log!(&self.logger, lvl, "", "config"; "key" => "address", "value" => %config.address);
+            slog_log!(&self.logger, lvl, "", "config"; "key" => "port", "value" => %config.port);
+            slog_log!(&self.logger, lvl, "", "config"; "key" => "workers", "value" => %config.workers);
+            slog_log!(&self.logger, lvl, "", "config"; "key" => "log_level", "value" => ?config.log_level);
             // not great, could there be a way to enumerate limits like we do for extras?
             if let Some(forms) = config.limits.get("forms") {
-                slog_info!(&self.0, "config"; "key" => "forms limit", "value" => ?forms);
+                slog_log!(&self.logger, lvl, "", "config"; "key" => "forms limit", "value" => ?forms);
             }
             if let Some(json) = config.limits.get("json") {
-                slog_info!(&self.0, "config"; "key" => "json limit", "value" => ?json);
+                slog_log!(&self.logger, lvl, "", "config"; "key" => "json limit", "value" => ?json);
             }
             if let Some(msgpack) = config.limits.get("msgpack") {
-                slog_info!(&self.0, "config"; "key" => "msgpack limit", "value" => ?msgpack);
+                slog_log!(&self.logger, lvl, "", "config"; "key" => "msgpack limit", "value" => ?msgpack);
             }
             for (key, val) in &config.extras {
-                slog_info!(&self.0, "config"; "key" => &key, "value" => ?val);
+                slog_log!(&self.logger, lvl, "", "config"; "key" => &key, "value" => ?val);
             }
         }
         // add managed logger so the user can use it in guards
-        Ok(rocket.manage(self.0.clone()))
+        Ok(rocket.manage(self.logger.clone()))
     }
 
     fn on_launch(&self, rocket: &Rocket) {
         for route in rocket.routes() {
             if route.rank < 0 {
-                slog_info!(&self.0, "route"; "base" => %route.base(), "path" => %route.uri, "method" => %route.method);
+                slog_log!(&self.logger, self.levels.routes, "", "route"; "base" => %route.base(), "path" => %route.uri, "method" => %route.method);
             } else {
-                slog_info!(&self.0, "route"; "base" => %route.base(), "path" => %route.uri, "rank" => %route.rank);
+                slog_log!(&self.logger, self.levels.routes, "", "route"; "base" => %route.base(), "path" => %route.uri, "rank" => %route.rank);
             }
         }
         // can't seem to get the list of Catchers?
@@ -203,21 +370,54 @@ impl Fairing for SlogFairing {
         let config = rocket.config();
         let scheme = if config.tls_enabled() { "https" } else { "http" };
         let addr = format!("{}://{}:{}", &scheme, &config.address, &config.port);
-        slog_info!(&self.0, "listening"; "address" => %addr);
+        slog_log!(&self.logger, self.levels.launch, "", "listening"; "address" => %addr);
 
     }
 
     fn on_request(&self, request: &mut Request, _: &Data) {
-        slog_info!(self.0, "request"; "method" => ?request.method(), "uri" => ?request.uri().to_string());
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let logger = self.logger.new(o!(
+            "request_id" => format!("{:x}", request_id),
+            "method" => request.method().to_string(),
+            "path" => request.uri().to_string(),
+        ));
+        let ignored = self.is_ignored(request);
+        // Skip the request_context closure entirely for filtered requests, so
+        // ignore_path/ignore_prefix also spare users its cost on hot endpoints.
+        let logger = if ignored { logger } else { self.contextualize(logger, request) };
+        let logger = Arc::new(logger);
+        if !ignored {
+            slog_log!(logger, self.levels.request, "", "request");
+        }
+        request.local_cache(|| RequestLog {
+            logger: logger.clone(),
+            start: Instant::now(),
+        });
     }
 
     fn on_response(&self, request: &Request, response: &mut Response) {
+        if self.is_ignored(request) {
+            return;
+        }
+
+        let request_log = request.local_cache(|| RequestLog {
+            logger: Arc::new(self.logger.new(o!())),
+            start: Instant::now(),
+        });
+        let elapsed_ms = {
+            let elapsed = request_log.start.elapsed();
+            elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_millis())
+        };
+
         let status = response.status();
+        let lvl = (self.status_level)(&status);
         let status = format!("{} {}", status.code, status.reason);
+        // `request_log.logger` was already contextualized in `on_request`; reuse it
+        // directly instead of folding the request_context fields in a second time.
         if let Some(ref route) = request.route() {
-            slog_info!(&self.0, "response"; "route" => %route, "status" => %status);
+            slog_log!(&request_log.logger, lvl, "", "response"; "route" => %route, "status" => %status, "elapsed_ms" => elapsed_ms);
         } else {
-            slog_info!(&self.0, "response"; "status" => %status);
+            slog_log!(&request_log.logger, lvl, "", "response"; "status" => %status, "elapsed_ms" => elapsed_ms);
         }
     }
 }